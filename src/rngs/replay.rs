@@ -0,0 +1,77 @@
+use rand_core::{impls, Error, RngCore};
+
+/// An [`RngCore`] that serves bytes from a fixed buffer, returning zeroes
+/// once the buffer is exhausted.
+///
+/// This mirrors the approach property-testing frameworks such as
+/// Hypothesis/Conjecture use to drive randomized tests from a finite byte
+/// buffer: a failing run's buffer can be captured, replayed bit for bit by
+/// feeding it back into a `ReplayRng`, and shrunk towards all-zeroes, since
+/// an all-zero buffer produces the "simplest" decisions (every bit reads as
+/// zero once the buffer runs out).
+pub struct ReplayRng<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ReplayRng<'a> {
+    /// Creates a new `ReplayRng` serving bytes from `buffer` in order.
+    /// Once `buffer` is exhausted, every further byte reads as zero.
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.buffer.get(self.position).copied().unwrap_or(0);
+        self.position += 1;
+        byte
+    }
+}
+
+impl<'a> RngCore for ReplayRng<'a> {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            *byte = self.next_byte();
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplayRng;
+    use rand_core::RngCore;
+
+    #[test]
+    fn test_replay_rng_serves_buffer_then_zeroes() {
+        let mut rng = ReplayRng::new(&[0x01, 0x02, 0x03, 0x04]);
+
+        let mut dest = [0u8; 6];
+        rng.fill_bytes(&mut dest);
+
+        assert_eq!(dest, [0x01, 0x02, 0x03, 0x04, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_replay_rng_empty_buffer_is_all_zero() {
+        let mut rng = ReplayRng::new(&[]);
+
+        assert_eq!(rng.next_u32(), 0);
+        assert_eq!(rng.next_u64(), 0);
+    }
+}
@@ -1,9 +1,15 @@
+use alloc::vec::Vec;
+
 use crate::RngCore;
 
 pub(crate) struct CoinFlipper<R: RngCore> {
     pub rng: R,
-    chunk: u32,
+    chunk: u64,
     chunk_remaining: u32,
+    /// When `Some`, records every bit consumed via [`Self::next`]/[`Self::all_next`],
+    /// in order, so the exact bit sequence behind a `shuffle`/`choose`/`gen_ratio` run
+    /// can be captured and later replayed with a [`crate::rngs::ReplayRng`].
+    recording: Option<Vec<bool>>,
 }
 
 impl<R: RngCore> CoinFlipper<R> {
@@ -12,6 +18,48 @@ impl<R: RngCore> CoinFlipper<R> {
             rng,
             chunk: 0,
             chunk_remaining: 0,
+            recording: None,
+        }
+    }
+
+    /// As [`Self::new`], but also records every bit consumed during subsequent
+    /// calls. See [`Self::recorded_bits`].
+    pub fn new_recording(rng: R) -> Self {
+        Self {
+            rng,
+            chunk: 0,
+            chunk_remaining: 0,
+            recording: Some(Vec::new()),
+        }
+    }
+
+    /// The bits consumed so far, in order, if this `CoinFlipper` was created
+    /// with [`Self::new_recording`]; `None` otherwise.
+    ///
+    /// Each `true` is a bit that read as zero; each `false` is a bit that read
+    /// as one. An all-`true` recording is the "simplest" possible sequence of
+    /// decisions, and is what a [`crate::rngs::ReplayRng`] fed an
+    /// all-zero buffer would reproduce.
+    pub fn recorded_bits(&self) -> Option<&[bool]> {
+        self.recording.as_deref()
+    }
+
+    #[inline]
+    fn record_bit(&mut self, bit_is_zero: bool) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(bit_is_zero);
+        }
+    }
+
+    #[inline]
+    fn record_bits(&mut self, zeros: u32, consumed: u32) {
+        if self.recording.is_some() {
+            for _ in 0..zeros {
+                self.record_bit(true);
+            }
+            if consumed > zeros {
+                self.record_bit(false);
+            }
         }
     }
 
@@ -19,20 +67,38 @@ impl<R: RngCore> CoinFlipper<R> {
     /// Returns true with a probability of 1 / denominator.
     /// Uses an expected two bits of randomness
     pub fn gen_ratio_one_over(&mut self, denominator: usize) -> bool {
+        self.gen_ratio_one_over_u64(denominator as u64)
+    }
+
+    #[inline]
+    /// Returns true with a probability of numerator / denominator
+    /// Uses an expected two bits of randomness
+    fn gen_ratio(&mut self, numerator: usize, denominator: usize) -> bool {
+        self.gen_ratio_u64(numerator as u64, denominator as u64)
+    }
+
+    #[inline]
+    /// Returns true with a probability of 1 / denominator.
+    /// Uses an expected two bits of randomness.
+    ///
+    /// The denominator is a `u64` rather than a `usize` so that the result
+    /// does not depend on the target's pointer width: a `usize` denominator
+    /// would be silently truncated on 32-bit targets.
+    pub fn gen_ratio_one_over_u64(&mut self, denominator: u64) -> bool {
         //For this case we can use an optimization, checking a large number of bits at once. If all those bits are successful, then we specialize
-        let n = usize::BITS - denominator.leading_zeros() - 1;
+        let n = u64::BITS - denominator.leading_zeros() - 1;
 
         if !self.all_next(n) {
             return false;
         }
 
-        self.gen_ratio(1 << n, denominator)
+        self.gen_ratio_u64(1 << n, denominator)
     }
 
     #[inline]
     /// Returns true with a probability of numerator / denominator
     /// Uses an expected two bits of randomness
-    fn gen_ratio(&mut self, mut numerator: usize, denominator: usize) -> bool {
+    fn gen_ratio_u64(&mut self, mut numerator: u64, denominator: u64) -> bool {
         // Explanation:
         // We are trying to return true with a probability of n / d
         // If n >= d, we can just return true
@@ -63,7 +129,7 @@ impl<R: RngCore> CoinFlipper<R> {
                 }
             } else {
                 //Special branch just for massive numbers.
-                //2n > usize::max >= d so 2n >= d
+                //2n > u64::max >= d so 2n >= d
                 if self.next() {
                     //heads
                     return true;
@@ -76,6 +142,83 @@ impl<R: RngCore> CoinFlipper<R> {
         true
     }
 
+    /// Picks one item from `iter` using weighted reservoir sampling, choosing
+    /// the item with a probability proportional to its weight.
+    ///
+    /// Unlike an index-based `choose_weighted`, this does not need to know the
+    /// total weight or the number of items up front, nor does it need random
+    /// access: it makes a single pass over `iter`, keeping a running total
+    /// weight `W` and replacing the current choice with probability `w / (W + w)`
+    /// whenever it sees an item of weight `w`, via [`Self::gen_ratio`]. This
+    /// costs an expected two bits of randomness per item. Returns `None` if
+    /// `iter` is empty.
+    ///
+    /// This is exposed to callers via [`crate::seq::IteratorRandom::choose_weighted_reservoir`].
+    pub(crate) fn choose_weighted_reservoir<T>(
+        &mut self,
+        iter: impl IntoIterator<Item = (T, usize)>,
+    ) -> Option<T> {
+        let mut iter = iter.into_iter();
+        let (mut chosen, mut total_weight) = iter.next()?;
+
+        for (item, weight) in iter {
+            let new_total_weight = total_weight + weight;
+            if self.gen_ratio(weight, new_total_weight) {
+                chosen = item;
+            }
+            total_weight = new_total_weight;
+        }
+
+        Some(chosen)
+    }
+
+    #[inline]
+    /// Returns true with a probability of `p`, where `p` is in the range `[0, 1]`.
+    /// Uses an expected two bits of randomness.
+    ///
+    /// This works by comparing the stream of random bits from `self.next()` against
+    /// the binary expansion of `p`, bit by bit, starting from the most significant bit.
+    /// At the first bit where they differ, the random bit being zero while `p`'s bit is
+    /// one means the random value is smaller than `p`, so we return true; otherwise we
+    /// return false. If every compared bit ties, we keep comparing further bits of `p`,
+    /// which is safe because `p` is an exactly representable `f64` and so its binary
+    /// expansion is finite (at most 53 significant bits, the last of which lies at
+    /// most 1074 places after the binary point).
+    ///
+    /// `p` outside `[0, 1]` is clamped to the nearest bound; `NaN` is treated as `0.0`.
+    pub fn gen_bool_f64(&mut self, mut p: f64) -> bool {
+        if p.is_nan() {
+            return false;
+        }
+        if p <= 0.0 {
+            return false;
+        }
+        if p >= 1.0 {
+            return true;
+        }
+
+        loop {
+            p *= 2.0;
+            let p_bit = p >= 1.0;
+            if p_bit {
+                p -= 1.0;
+            }
+
+            //`next` returns true when the random bit is zero
+            let random_bit_is_zero = self.next();
+
+            if random_bit_is_zero == p_bit {
+                return p_bit && random_bit_is_zero;
+            }
+
+            if p == 0.0 {
+                //`p`'s binary expansion has terminated, so every remaining bit of `p`
+                //is zero and the random value can never be smaller than `p` from here
+                return false;
+            }
+        }
+    }
+
     #[inline]
     /// Consume one bit of randomness
     /// Has a one in two chance of returning true
@@ -83,12 +226,15 @@ impl<R: RngCore> CoinFlipper<R> {
         if let Some(new_rem) = self.chunk_remaining.checked_sub(1) {
             self.chunk_remaining = new_rem;
         } else {
-            self.chunk = self.rng.next_u32();
-            self.chunk_remaining = u32::BITS - 1;
+            //Refilling from `next_u64` rather than `next_u32` halves the number of
+            //rng calls needed for the same number of bits on modern 64-bit PRNGs.
+            self.chunk = self.rng.next_u64();
+            self.chunk_remaining = u64::BITS - 1;
         };
 
         let result = self.chunk.trailing_zeros() > 0; //TODO check if there is a faster test the last bit
         self.chunk = self.chunk.wrapping_shr(1);
+        self.record_bit(result);
         result
     }
 
@@ -101,19 +247,22 @@ impl<R: RngCore> CoinFlipper<R> {
         while self.chunk_remaining < n {
             //Check we have enough randomness left
             if zeros >= self.chunk_remaining {
+                self.record_bits(self.chunk_remaining, self.chunk_remaining);
                 n -= self.chunk_remaining; // Remaining bits are zeroes, we will need to generate more bits and continue
             } else {
+                self.record_bits(zeros, zeros + 1);
                 self.chunk_remaining -= zeros + 1; //There was a one in the remaining bits so we can consume it and continue
                 self.chunk >>= zeros + 1;
                 return false;
             }
-            self.chunk = self.rng.next_u32();
-            self.chunk_remaining = u32::BITS;
+            self.chunk = self.rng.next_u64();
+            self.chunk_remaining = u64::BITS;
             zeros = self.chunk.trailing_zeros();
         }
 
         let result = zeros >= n;
         let bits_to_consume = if result { n } else { zeros + 1 };
+        self.record_bits(zeros.min(bits_to_consume), bits_to_consume);
         self.chunk = self.chunk.wrapping_shr(bits_to_consume);
         self.chunk_remaining = self.chunk_remaining.saturating_sub(bits_to_consume);
 
@@ -129,6 +278,7 @@ mod tests {
     use rand_core::Error;
 
     use crate::prelude::StdRng;
+    use crate::rngs::ReplayRng;
     use crate::seq::coin_flipper::CoinFlipper;
     use crate::{Rng, RngCore, SeedableRng};
 
@@ -147,7 +297,9 @@ mod tests {
 
         let mut count = 0;
         for _ in 0..LENGTH {
-            if coin_flipper.gen_ratio_one_over((2_i64.pow(33) + 1) as usize) {
+            // `2^33 + 1` does not fit in a 32-bit `usize`, so this must go through the
+            // `u64` based variant to behave the same way on every target.
+            if coin_flipper.gen_ratio_one_over_u64(2_u64.pow(33) + 1) {
                 count += 1;
             }
         }
@@ -159,7 +311,7 @@ mod tests {
         //     coin_flipper.rng.count, average_gens
         // );
         // println!("Count: {count}");
-        assert_contains(15.5..16.5, &average_gens); //Should be about 16
+        assert_contains(31.5..32.5, &average_gens); //Should be about 32 (64 bit chunk / 2 bits per gen)
 
         assert!(count < 2); //Should not get it twice
     }
@@ -188,7 +340,7 @@ mod tests {
         let mean = (count as f64) / RUNS as f64;
 
         //println!("Mean: {mean}");
-        assert_contains(15.5..16.5, &average_gens); //Should be about 16 (32 bit / 2 bits per gen)
+        assert_contains(31.5..32.5, &average_gens); //Should be about 32 (64 bit / 2 bits per gen)
         assert_contains(0.45..0.55, &mean); //Should be about 0.5
     }
 
@@ -226,11 +378,148 @@ mod tests {
 
         //println!("mean: {mean}, variance: {variance}, standard deviation: {standard_deviation}");
 
-        assert_contains(15.5..16.5, &average_gens); //Should be just over 16 gens per gen_ratio
+        assert_contains(31.5..32.5, &average_gens); //Should be just over 32 gens per gen_ratio
         assert_contains(0.95..1.05, &mean); //Should be about 1 because we are adjusting
         assert_contains(0.0..10.0, &standard_deviation);
     }
 
+    #[test]
+    pub fn test_gen_bool_f64_edge_cases() {
+        let rng = get_rng();
+        let mut coin_flipper = CoinFlipper::new(rng);
+
+        assert!(!coin_flipper.gen_bool_f64(0.0));
+        assert!(coin_flipper.gen_bool_f64(1.0));
+    }
+
+    #[test]
+    pub fn test_gen_bool_f64() {
+        let rng = get_rng();
+        let mut coin_flipper = CoinFlipper::new(rng);
+
+        for numerator in [1u32, 3, 5, 7] {
+            let p = (numerator as f64) / 8.0;
+            let mut count = 0;
+            for _ in 0..RUNS {
+                if coin_flipper.gen_bool_f64(p) {
+                    count += 1;
+                }
+            }
+
+            let mean = (count as f64) / RUNS as f64;
+            assert_contains((p - 0.05)..(p + 0.05), &mean);
+        }
+    }
+
+    #[test]
+    pub fn test_choose_weighted_reservoir_empty() {
+        let rng = get_rng();
+        let mut coin_flipper = CoinFlipper::new(rng);
+
+        let chosen = coin_flipper.choose_weighted_reservoir(core::iter::empty::<(u32, usize)>());
+        assert_eq!(chosen, None);
+    }
+
+    #[test]
+    pub fn test_choose_weighted_reservoir() {
+        let rng = get_rng();
+        let mut coin_flipper = CoinFlipper::new(rng);
+
+        let items = [(0usize, 1usize), (1, 2), (2, 3), (3, 4)];
+        let total_weight: usize = items.iter().map(|&(_, w)| w).sum();
+
+        let mut counts = [0usize; 4];
+        for _ in 0..RUNS {
+            let chosen = coin_flipper
+                .choose_weighted_reservoir(items.iter().copied())
+                .unwrap();
+            counts[chosen] += 1;
+        }
+
+        for &(item, weight) in items.iter() {
+            let expected = (weight as f64) / (total_weight as f64);
+            let actual = (counts[item] as f64) / (RUNS as f64);
+            assert_contains((expected - 0.05)..(expected + 0.05), &actual);
+        }
+    }
+
+    #[test]
+    pub fn test_chunk_refills_from_next_u64() {
+        let rng = get_rng();
+        let mut coin_flipper = CoinFlipper::new(rng);
+
+        for d in START..=LENGTH {
+            for _ in 0..RUNS {
+                coin_flipper.gen_ratio_one_over(d);
+            }
+        }
+
+        let average_gens = ((RUNS * LENGTH) as f64) / (coin_flipper.rng.count as f64);
+
+        // A 64-bit chunk refilled from `next_u64` yields about twice as many
+        // decisions per rng call as the previous 32-bit, `next_u32`-backed chunk.
+        assert_contains(31.5..32.5, &average_gens);
+    }
+
+    #[test]
+    pub fn test_recording_replays_deterministically() {
+        let rng = get_rng();
+        let mut coin_flipper = CoinFlipper::new_recording(rng);
+
+        for d in 2..20 {
+            coin_flipper.gen_ratio_one_over(d);
+        }
+
+        let recorded: alloc::vec::Vec<bool> = coin_flipper.recorded_bits().unwrap().to_vec();
+
+        // The recorded bit sequence should not be trivially all-zero,
+        // since `StdRng` produces genuinely mixed bits.
+        assert!(recorded.iter().any(|&bit_is_zero| !bit_is_zero));
+
+        // Feeding the exact recorded bit sequence back through a `ReplayRng`
+        // and re-running the same decisions should consume and re-record the
+        // identical bit sequence, proving the recording is a faithful,
+        // replayable account of the original run.
+        let packed = pack_bits(&recorded);
+        let replay_rng = ReplayRng::new(&packed);
+        let mut replaying_flipper = CoinFlipper::new_recording(replay_rng);
+        for d in 2..20 {
+            replaying_flipper.gen_ratio_one_over(d);
+        }
+        let replayed: alloc::vec::Vec<bool> =
+            replaying_flipper.recorded_bits().unwrap().to_vec();
+
+        assert_eq!(replayed, recorded);
+    }
+
+    #[test]
+    pub fn test_all_zero_buffer_is_simplest_replay() {
+        // An all-zero buffer is the "simplest" possible input: every bit reads
+        // as zero, so `next` always takes its first branch.
+        let replay_rng = ReplayRng::new(&[]);
+        let mut replaying_flipper = CoinFlipper::new_recording(replay_rng);
+        for d in 2..20 {
+            replaying_flipper.gen_ratio_one_over(d);
+        }
+        let replayed: alloc::vec::Vec<bool> =
+            replaying_flipper.recorded_bits().unwrap().to_vec();
+
+        assert!(replayed.iter().all(|&bit_is_zero| bit_is_zero));
+    }
+
+    /// Packs a bit sequence produced by [`CoinFlipper::recorded_bits`] into bytes
+    /// suitable for [`ReplayRng`], matching the bit order `CoinFlipper` consumes
+    /// them in (least-significant bit of each `u64` chunk first).
+    fn pack_bits(bits: &[bool]) -> alloc::vec::Vec<u8> {
+        let mut bytes = alloc::vec![0u8; bits.len().div_ceil(8)];
+        for (k, &bit_is_zero) in bits.iter().enumerate() {
+            if !bit_is_zero {
+                bytes[k / 8] |= 1 << (k % 8);
+            }
+        }
+        bytes
+    }
+
     fn get_rng() -> CountingRng<StdRng> {
         let inner = StdRng::seed_from_u64(SEED);
         CountingRng {
@@ -0,0 +1,30 @@
+mod coin_flipper;
+
+use coin_flipper::CoinFlipper;
+
+use crate::Rng;
+
+/// Extension trait adding iterator-based random sampling.
+///
+/// This complements `SliceRandom::choose_weighted`, which needs to know the
+/// total weight and requires random access up front; `choose_weighted_reservoir`
+/// instead makes a single pass over the iterator, so it works with unknown
+/// length iterators and unknown total weight.
+pub trait IteratorRandom: Iterator + Sized {
+    /// Chooses one item from this iterator of `(item, weight)` pairs, with
+    /// probability proportional to each item's weight.
+    ///
+    /// This makes a single pass over the iterator using weighted reservoir
+    /// sampling, so it does not need to know the total weight or the number
+    /// of items up front, nor does it need random access. Returns `None` if
+    /// the iterator is empty.
+    fn choose_weighted_reservoir<T, R>(self, rng: &mut R) -> Option<T>
+    where
+        Self: Iterator<Item = (T, usize)>,
+        R: Rng + ?Sized,
+    {
+        CoinFlipper::new(rng).choose_weighted_reservoir(self)
+    }
+}
+
+impl<I: Iterator> IteratorRandom for I {}